@@ -15,6 +15,7 @@ use axum::{
 use serde::{Serialize, Deserialize};
 use tera::{Tera, Context};
 use std::collections::HashMap;
+use tera_shortcodes::ShortcodeArgs;
 
 const ADDRESS: &str = "127.0.0.1:8080";
 
@@ -33,35 +34,42 @@ struct ProductsShortcode {
 }
 
 // {{ shortcode(display="products", limit=4) | safe }}
+//
+// Takes the products backend's base URL as a parameter instead of reading
+// the `ADDRESS` const directly, so it can be registered as a closure that
+// captures that URL (or, in a real app, an `Arc<AppState>` carrying a DB
+// pool or per-environment config) rather than a bare function pointer.
 fn products_shortcode_fn(
+    base_url: &str,
     args: &HashMap<String, tera::Value>,
-) -> String {
+    _content: Option<&str>,
+) -> tera::Result<String> {
+
+    let args = ShortcodeArgs::new(args);
 
     let mut parameters = vec![];
 
-    if let Some(limit) = args.get("limit") {
-        parameters.push(format!("limit={}", limit.as_str()
-            .unwrap()
-            .trim_matches(|c| c == '"' || c == '\'')));
+    if let Some(limit) = args.get_int("limit") {
+        parameters.push(format!("limit={}", limit));
     }
 
-    if let Some(orderby) = args.get("orderby") {
-        parameters.push(format!("orderby={}", orderby.as_str()
-            .unwrap()
-            .trim_matches(|c| c == '"' || c == '\'')));
+    if let Some(orderby) = args.get_str("orderby") {
+        parameters.push(format!("orderby={}", orderby));
     }
 
-    let url = format!("http://{}/products?{}", ADDRESS, parameters.join("&"));
+    let url = format!("http://{}/products?{}", base_url, parameters.join("&"));
 
-    tera_shortcodes::fetch_shortcode_js(
+    Ok(tera_shortcodes::fetch_shortcode_js(
         &url,
-        Some("get"), 
+        Some("get"),
+        None,
+        None,
         None,
-    )
+    ))
 
     // shortcodes::fetch_shortcode(
-    //    url, 
-    //    Some("get"), 
+    //    url,
+    //    Some("get"),
     //    None,
     // )
 }
@@ -139,63 +147,41 @@ async fn products(
 
 fn another_shortcode_fn(
     args: &HashMap<String, tera::Value>,
-) -> String {
-    let width = match args.get("width") {
-        Some(value) => value
-            .as_str()
-            .unwrap()
-            .trim_matches(|c| c == '"' || c == '\''),
-        None => "200",
-    };
-    let height = match args.get("height") {
-        Some(value) => value
-            .as_str()
-            .unwrap()
-            .trim_matches(|c| c == '"' || c == '\''),
-        None => "200",
-    };
-    let image_src = match args.get("image_src") {
-        Some(value) => value
-            .as_str()
-            .unwrap()
-            .trim_matches(|c| c == '"' || c == '\''),
-        None => "No image attribute specified",
-    };
+    _content: Option<&str>,
+) -> tera::Result<String> {
+    let args = ShortcodeArgs::new(args);
+
+    let width = args.get_str_or("width", "200");
+    let height = args.get_str_or("height", "200");
+    let image_src = args.get_str_or("image_src", "No image attribute specified");
 
-    format!(r#"<img src="{}" width="{}" height="{}">"#, image_src, width, height)
+    Ok(format!(r#"<img src="{}" width="{}" height="{}">"#, image_src, width, height))
 }
 
 fn my_shortcode_fn(
     args: &HashMap<String, tera::Value>,
-) -> String {
-
-    let foo = match args.get("foo") {
-        Some(value) => value
-            .as_str()
-            .unwrap()
-            .trim_matches(|c| c == '"' || c == '\''),
-        None => "no foo",
-    };
-    let bar = match args.get("bar") {
-        Some(value) => value
-            .as_str()
-            .unwrap()
-            .trim_matches(|c| c == '"' || c == '\''),
-        None => "no bar",
-    };
+    _content: Option<&str>,
+) -> tera::Result<String> {
+
+    let args = ShortcodeArgs::new(args);
+
+    let foo = args.get_str_or("foo", "no foo");
+    let bar = args.get_str_or("bar", "no bar");
 
     let json_body = serde_json::to_string(&DataTest {
-        foo: foo.to_string(),
-        bar: bar.to_string(),
+        foo,
+        bar,
     }).unwrap();
 
     let url = format!("http://{}/data", ADDRESS);
 
-    tera_shortcodes::fetch_shortcode_js(
+    Ok(tera_shortcodes::fetch_shortcode_js(
         &url,
         Some("post"),
-        Some(&json_body)
-    )
+        Some(&json_body),
+        None,
+        None,
+    ))
 }
 
 #[derive(Serialize, Deserialize)]
@@ -234,10 +220,14 @@ async fn test(
 #[tokio::main]
 async fn main() {
 
+    let products_base_url = ADDRESS.to_string();
+
     let shortcodes = tera_shortcodes::Shortcodes::new()
         .register("my_shortcode", my_shortcode_fn)
         .register("another_shortcode", another_shortcode_fn)
-        .register("products", products_shortcode_fn);
+        .register("products", move |args, content| {
+            products_shortcode_fn(&products_base_url, args, content)
+        });
 
     let mut tera = Tera::new("examples/templates/**/*").unwrap();
 
@@ -0,0 +1,73 @@
+//
+// typed shortcode argument accessor
+//
+
+use std::collections::HashMap;
+
+/// A thin, read-only wrapper around the `HashMap<String, tera::Value>` every
+/// shortcode function receives, offering typed accessors that coerce from
+/// either a real `tera::Value` or a quoted string, and strip surrounding
+/// quotes once in a single place instead of every call site repeating
+/// `.as_str().unwrap().trim_matches(...)`.
+///
+/// Inspired by WordPress's `shortcode_atts`, but without the defaults
+/// merging: use [`ShortcodeArgs::get_str_or`], or `unwrap_or` on the other
+/// accessors' `Option`, for that instead.
+pub struct ShortcodeArgs<'a>(&'a HashMap<String, tera::Value>);
+
+impl<'a> ShortcodeArgs<'a> {
+
+    /// Wraps a shortcode's raw argument map for typed access.
+    pub fn new(args: &'a HashMap<String, tera::Value>) -> Self {
+        ShortcodeArgs(args)
+    }
+
+    /// Returns `key` as a `String` when its `tera::Value` is a string, number
+    /// or bool, with a quoted string's surrounding quotes stripped; `None`
+    /// for any other variant (null, array, object).
+    pub fn get_str(&self, key: &str) -> Option<String> {
+        match self.0.get(key)? {
+            tera::Value::String(s) => Some(s.trim_matches(|c| c == '"' || c == '\'').to_owned()),
+            tera::Value::Number(n) => Some(n.to_string()),
+            tera::Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Like [`ShortcodeArgs::get_str`], but falls back to `default` when
+    /// `key` is missing or isn't a string/number/bool.
+    pub fn get_str_or(&self, key: &str, default: &str) -> String {
+        self.get_str(key).unwrap_or_else(|| default.to_owned())
+    }
+
+    /// Returns `key` as an `i64`, parsing it out of a quoted string if
+    /// that's how it was passed.
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match self.0.get(key)? {
+            tera::Value::Number(n) => n.as_i64(),
+            tera::Value::String(s) => s.trim_matches(|c| c == '"' || c == '\'').parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns `key` as an `f64`, parsing it out of a quoted string if
+    /// that's how it was passed.
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        match self.0.get(key)? {
+            tera::Value::Number(n) => n.as_f64(),
+            tera::Value::String(s) => s.trim_matches(|c| c == '"' || c == '\'').parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns `key` as a `bool`, parsing `"true"`/`"false"` out of a
+    /// quoted string if that's how it was passed.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.0.get(key)? {
+            tera::Value::Bool(b) => Some(*b),
+            tera::Value::String(s) => s.trim_matches(|c| c == '"' || c == '\'').parse().ok(),
+            _ => None,
+        }
+    }
+
+}
@@ -4,19 +4,39 @@
 
 use tera::{Result, Function};
 use std::collections::HashMap;
+use std::time::Duration;
 use once_cell::sync::Lazy;
 
+mod args;
+mod parser;
+
+pub use args::ShortcodeArgs;
+
 static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| reqwest::Client::new());
 
 // const ROBOTS_TXT: &'static str = "Link for Robots (No JavaScript)";
 
+/// The signature every registered shortcode handler must implement.
+///
+/// The second parameter carries the (already recursively expanded) inner
+/// content for an enclosing `[name]...[/name]` bracket shortcode, or `None`
+/// for an inline `{{ shortcode(display="...") }}` call and for a
+/// self-closing `[name]` bracket shortcode. Returning `Err` surfaces as a
+/// real Tera template error (or inline error text from [`Shortcodes::expand`])
+/// instead of panicking, e.g. when a required argument is missing.
+///
+/// Boxed rather than a bare function pointer so [`Shortcodes::register`] can
+/// accept a closure that captures its own state, e.g. a database pool or an
+/// API base URL.
+pub type ShortcodeFn = Box<dyn Fn(&HashMap<String, tera::Value>, Option<&str>) -> Result<String> + Send + Sync>;
+
 /// A struct that manages shortcode functions for use in Tera templates.
-/// 
+///
 /// # Fields
-/// 
-/// - `functions`: A `HashMap` where the key is the shortcode display name (a `String`), and the value is a function pointer that takes a reference to a `HashMap` of arguments and returns a `String` representing the generated content.
+///
+/// - `functions`: A `HashMap` where the key is the shortcode display name (a `String`), and the value is a boxed [`ShortcodeFn`] closure that takes a reference to a `HashMap` of arguments plus the optional enclosed content, and returns a `Result<String>` with the generated content.
 pub struct Shortcodes {
-    pub functions: HashMap<String, fn(&HashMap<String, tera::Value>) -> String>,
+    pub functions: HashMap<String, ShortcodeFn>,
 }
 
 impl Shortcodes {
@@ -32,34 +52,99 @@ impl Shortcodes {
         }
     }
 
+    /// Creates a new `Shortcodes` instance and sets the maximum number of
+    /// entries the shared [`fetch_shortcode_cached`] response cache will
+    /// hold before it starts evicting the oldest one to make room.
+    ///
+    /// The cache is process-wide, so this only needs to be called once;
+    /// calling it again simply resizes the same cache.
+    ///
+    /// # Parameters
+    ///
+    /// - `max_entries`: The maximum number of distinct `(url, method, body)` responses to keep cached.
+    ///
+    /// # Returns
+    ///
+    /// A `Shortcodes` struct with an empty `functions` map, ready to chain `.register(...)` calls.
+    pub fn with_cache(max_entries: usize) -> Self {
+        CACHE.lock().unwrap().max_entries = max_entries;
+        Self::new()
+    }
+
     /// Registers a new shortcode function in the `Shortcodes` struct.
-    /// 
+    ///
+    /// Accepts any closure or function matching [`ShortcodeFn`]'s signature,
+    /// including one that captures its own state (an `Arc<AppState>`, a
+    /// database pool, an API base URL) via `move`, instead of requiring a
+    /// bare function pointer with no way to carry that state.
+    ///
     /// # Parameters
-    /// 
+    ///
     /// - `display`: The shortcode display name as a `&str`, which will be used as the key in the `functions` map.
-    /// - `shortcode_fn`: A function pointer that takes a `HashMap` of arguments and returns a `String`.
-    /// 
+    /// - `shortcode_fn`: A closure that takes a `HashMap` of arguments and the optional enclosed content, and returns a `Result<String>`.
+    ///
     /// # Returns
-    /// 
+    ///
     /// An updated instance of `Shortcodes` with the newly registered shortcode function.
-    /// 
+    ///
     /// # Example
-    /// 
+    ///
     /// ```rust
     /// use tera_shortcodes::Shortcodes;
-    /// 
-    /// let shortcodes = Shortcodes::new().register("example", |args| {
-    ///     "Shortcode output".to_string()
+    ///
+    /// let shortcodes = Shortcodes::new().register("example", |_args, _content| {
+    ///     Ok("Shortcode output".to_string())
     /// });
     /// ```
-    pub fn register(mut self,
+    pub fn register<F>(mut self,
         display: &str,
-        shortcode_fn: fn(&HashMap<String, tera::Value>) -> String,
-    ) -> Self {
-        self.functions.insert(display.to_owned(), shortcode_fn);
+        shortcode_fn: F,
+    ) -> Self
+    where
+        F: Fn(&HashMap<String, tera::Value>, Option<&str>) -> Result<String> + Send + Sync + 'static,
+    {
+        self.functions.insert(display.to_owned(), Box::new(shortcode_fn));
         self
     }
 
+    /// Expands WordPress-style bracket shortcodes embedded in a raw content
+    /// string, independently of Tera template rendering.
+    ///
+    /// Scans `raw` for `[name attr="v"]inner[/name]` and self-closing
+    /// `[name attr="v"]` tags, parses each tag's attributes into the same
+    /// `HashMap<String, tera::Value>` the registered functions already
+    /// consume, and invokes the matching function registered via
+    /// [`Shortcodes::register`]. Shortcodes nested inside enclosed content
+    /// are expanded recursively before the outer shortcode is invoked.
+    /// Literal brackets can be produced with `[[name]]`, which collapses to
+    /// `[name]` without being treated as a tag. A name with no registered
+    /// function is passed through untouched.
+    ///
+    /// # Parameters
+    ///
+    /// - `raw`: The raw content string to scan, e.g. a CMS/database field.
+    ///
+    /// # Returns
+    ///
+    /// The content with every recognised shortcode replaced by its
+    /// generated output.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tera_shortcodes::Shortcodes;
+    ///
+    /// let shortcodes = Shortcodes::new().register("gallery", |_args, content| {
+    ///     Ok(format!("<div class=\"gallery\">{}</div>", content.unwrap_or("")))
+    /// });
+    ///
+    /// let html = shortcodes.expand("[gallery]picture one[/gallery]");
+    /// assert_eq!(html, "<div class=\"gallery\">picture one</div>");
+    /// ```
+    pub fn expand(&self, raw: &str) -> String {
+        parser::expand(&self.functions, raw)
+    }
+
 }
 
 impl Function for Shortcodes {
@@ -75,22 +160,21 @@ impl Function for Shortcodes {
     /// A `Result<tera::Value>` that contains the generated content as a `String` or an error message if the display name is missing or unknown.
     /// 
     /// # Error Handling
-    /// 
+    ///
     /// - If the `display` attribute is missing, it returns an error message `"Missing display attribute"`.
     /// - If no function is registered for the given display name, it returns an error message `"Unknown shortcode display name: <display>"`.
+    /// - If the registered function itself returns `Err`, that error is propagated as a real Tera template error.
     fn call(&self,
         args: &HashMap<String, tera::Value>,
     ) -> Result<tera::Value> {
 
-        let display = match args.get("display") {
-            Some(value) => value.as_str()
-                .unwrap()
-                .trim_matches(|c| c == '"' || c == '\''),
+        let display = match ShortcodeArgs::new(args).get_str("display") {
+            Some(display) => display,
             None => return Ok(tera::Value::String("Missing display attribute".to_owned())),
         };
 
-        let fragment = match self.functions.get(display) {
-            Some(shortcode_fn) => shortcode_fn(args),
+        let fragment = match self.functions.get(display.as_str()) {
+            Some(shortcode_fn) => shortcode_fn(args, None)?,
             None => {
                 return Ok(tera::Value::String(format!("Unknown shortcode display name: {}", display)))
             },
@@ -112,8 +196,12 @@ impl Function for Shortcodes {
 /// - `method`: An optional HTTP method, either `GET` or `POST`. Defaults to `GET` if `None` is provided.
 /// - `json_body`: An optional JSON string for the request body when using the `POST` method. Defaults to
 ///   an empty JSON object (`{}`) if `None` is provided. Ignored if the method is `GET`.
-/// - `alt`: An optional alternative content to display in a `<noscript>` block for crawlers/robots without JavaScript. 
+/// - `alt`: An optional alternative content to display in a `<noscript>` block for crawlers/robots without JavaScript.
 ///   This is only used if the method is `GET`. Defaults to `None`.
+/// - `nonce`: An optional Content-Security-Policy nonce. When provided, it is emitted as
+///   `<script nonce="...">` on the generated `<script>` tag, and copied onto the `<script>` elements
+///   `reScript` re-creates from the fetched content, so the loader keeps working under a strict
+///   `script-src 'nonce-...'` policy.
 ///
 /// # Returns
 ///
@@ -127,16 +215,21 @@ impl Function for Shortcodes {
 /// If the `GET` method is used and `alt` is provided, the function also includes a `<noscript>` fallback
 /// to display a link in case JavaScript is disabled or not supported.
 ///
+/// `url` and `json_body` are escaped before being interpolated into the generated script, so neither
+/// quotes nor a `</script>` sequence in either value can break out of the `<script>` block. `url` and
+/// `alt` are likewise HTML-escaped before being interpolated into the `<noscript>` fallback.
+///
 /// # Example
 ///
 /// ```rust
 /// use tera_shortcodes::fetch_shortcode_js;
-/// 
+///
 /// let js_code = fetch_shortcode_js(
-///     "https://example.com/data", 
-///     Some("POST"), 
-///     Some("{\"key\": \"value\"}"), 
-///     Some("No JavaScript fallback")
+///     "https://example.com/data",
+///     Some("POST"),
+///     Some("{\"key\": \"value\"}"),
+///     Some("No JavaScript fallback"),
+///     Some("abc123"),
 /// );
 ///
 /// println!("{}", js_code);
@@ -154,15 +247,19 @@ pub fn fetch_shortcode_js(
     method: Option<&str>,
     json_body: Option<&str>,
     alt: Option<&str>,
+    nonce: Option<&str>,
 ) -> String {
 
     let method = method.unwrap_or("GET");
     let json_body = json_body.unwrap_or("{}");
 
+    let js_url = escape_for_script(&serde_json::to_string(url).unwrap());
+    let js_body = escape_for_script(json_body);
+
     let fetch_js = match method.to_lowercase().as_str() {
-        "get" => format!(r#"const response = await fetch("{}");"#, url),
+        "get" => format!(r#"const response = await fetch({});"#, js_url),
         "post" => format!(r#"
-const request = new Request("{}", {{
+const request = new Request({}, {{
     headers: (() => {{
         const headers = new Headers();
         headers.append("Content-Type", "application/json");
@@ -171,15 +268,20 @@ const request = new Request("{}", {{
     method: "POST",
     body: JSON.stringify({}),
 }});
-const response = await fetch(request);"#, url, json_body),
+const response = await fetch(request);"#, js_url, js_body),
         _ => return format!(r#"<output style="background-color:#f44336;color:#fff;padding:6px;">
 Invalid method {} for url {} (only GET and POST methods available)
 </output>"#, method, url),
     };
 
+    let nonce_attr = match nonce {
+        Some(nonce) => format!(r#" nonce="{}""#, nonce),
+        None => String::new(),
+    };
+
     // reScript function ia a trick to make the Javascript code work when inserted.
     // Replace it with another clone element script.
-    let js_code = format!(r#"<script>
+    let js_code = format!(r#"<script{}>
 (function () {{
     async function fetchShortcodeData() {{
         try {{
@@ -193,6 +295,7 @@ Invalid method {} for url {} (only GET and POST methods available)
             return "";
         }}
     }}
+    let currentScript;
     function reScript(helper) {{
         for (const node of helper.childNodes) {{
             if (node.hasChildNodes()) {{
@@ -201,13 +304,14 @@ Invalid method {} for url {} (only GET and POST methods available)
             if (node.nodeName === 'SCRIPT') {{
                 const script = document.createElement('script');
                 script.type = "text/javascript";
+                script.nonce = currentScript.nonce;
                 script.textContent = node.textContent;
                 node.replaceWith(script);
             }}
         }}
     }}
     (async () => {{
-        const currentScript = document.currentScript;
+        currentScript = document.currentScript;
         const content = await fetchShortcodeData();
         // console.log(content);
         const helper = document.createElement('div');
@@ -219,16 +323,181 @@ Invalid method {} for url {} (only GET and POST methods available)
     }})();
 }})();
 </script>"#,
-    fetch_js);
+    nonce_attr, fetch_js);
 
     if method.to_lowercase().as_str() == "get" && alt.is_some() {
         let alt = alt.unwrap();
-        js_code.to_string() + &format!(r#"<noscript><a href="{}">{}</a></noscript>"#, url, alt)
+        js_code.to_string() + &format!(r#"<noscript><a href="{}">{}</a></noscript>"#, escape_html(url), escape_html(alt))
     } else {
         js_code
     }
 }
 
+/// Like [`fetch_shortcode_js`], but takes a [`FetchOptions`] so the
+/// generated loader can send custom headers and bearer auth, and use any of
+/// `GET`, `POST`, `PUT`, `PATCH`, or `DELETE` instead of only `GET`/`POST`.
+///
+/// # Parameters
+///
+/// - `url`: A string slice containing the URL to which the HTTP request will be made.
+/// - `options`: The method, body, headers, and optional bearer token to send. See [`FetchOptions`].
+/// - `alt`: An optional alternative content to display in a `<noscript>` block for crawlers/robots without JavaScript.
+///   Only used if the method is `GET`.
+/// - `nonce`: An optional Content-Security-Policy nonce, as in [`fetch_shortcode_js`].
+///
+/// # Returns
+///
+/// A `String` containing the generated JavaScript code, or an HTML `<output>` element with an
+/// error message if `options.method` isn't one of the supported methods.
+///
+/// # Example
+///
+/// ```rust
+/// use tera_shortcodes::{fetch_shortcode_js_with, FetchOptions};
+///
+/// let js_code = fetch_shortcode_js_with(
+///     "https://example.com/api/data",
+///     &FetchOptions::new()
+///         .method("PATCH")
+///         .json_body(r#"{"key": "value"}"#)
+///         .bearer_token("my-api-token"),
+///     None,
+///     Some("abc123"),
+/// );
+///
+/// println!("{}", js_code);
+/// ```
+pub fn fetch_shortcode_js_with(
+    url: &str,
+    options: &FetchOptions,
+    alt: Option<&str>,
+    nonce: Option<&str>,
+) -> String {
+
+    let method = options.method.as_deref().unwrap_or("GET");
+    let json_body = options.json_body.as_deref().unwrap_or("{}");
+    let method_upper = method.to_uppercase();
+
+    if !matches!(method_upper.as_str(), "GET" | "POST" | "PUT" | "PATCH" | "DELETE") {
+        return format!(r#"<output style="background-color:#f44336;color:#fff;padding:6px;">
+Invalid method {} for url {} (only GET, POST, PUT, PATCH, and DELETE methods available)
+</output>"#, method, url);
+    }
+
+    let js_url = escape_for_script(&serde_json::to_string(url).unwrap());
+    let js_body = escape_for_script(json_body);
+
+    let mut header_lines = String::new();
+    if method_upper != "GET" && !has_content_type(&options.headers) {
+        header_lines.push_str("        headers.append(\"Content-Type\", \"application/json\");\n");
+    }
+    for (key, value) in &options.headers {
+        header_lines.push_str(&format!(
+            "        headers.append({}, {});\n",
+            escape_for_script(&serde_json::to_string(key).unwrap()),
+            escape_for_script(&serde_json::to_string(value).unwrap()),
+        ));
+    }
+    if let Some(token) = &options.bearer_token {
+        header_lines.push_str(&format!(
+            "        headers.append(\"Authorization\", {});\n",
+            escape_for_script(&serde_json::to_string(&format!("Bearer {}", token)).unwrap()),
+        ));
+    }
+
+    let body_line = if method_upper == "GET" {
+        String::new()
+    } else {
+        format!("    body: JSON.stringify({}),\n", js_body)
+    };
+
+    let fetch_js = format!(r#"
+const request = new Request({}, {{
+    headers: (() => {{
+        const headers = new Headers();
+{}        return headers;
+    }})(),
+    method: "{}",
+{}}});
+const response = await fetch(request);"#, js_url, header_lines, method_upper, body_line);
+
+    let nonce_attr = match nonce {
+        Some(nonce) => format!(r#" nonce="{}""#, nonce),
+        None => String::new(),
+    };
+
+    // reScript function ia a trick to make the Javascript code work when inserted.
+    // Replace it with another clone element script.
+    let js_code = format!(r#"<script{}>
+(function () {{
+    async function fetchShortcodeData() {{
+        try {{
+            {}
+            if (!response.ok) {{
+                throw new Error(`HTTP error! Status: ${{response.status}}`);
+            }}
+            return await response.text();
+        }} catch (error) {{
+            console.error("Fetch failed:", error);
+            return "";
+        }}
+    }}
+    let currentScript;
+    function reScript(helper) {{
+        for (const node of helper.childNodes) {{
+            if (node.hasChildNodes()) {{
+                reScript(node);
+            }}
+            if (node.nodeName === 'SCRIPT') {{
+                const script = document.createElement('script');
+                script.type = "text/javascript";
+                script.nonce = currentScript.nonce;
+                script.textContent = node.textContent;
+                node.replaceWith(script);
+            }}
+        }}
+    }}
+    (async () => {{
+        currentScript = document.currentScript;
+        const content = await fetchShortcodeData();
+        // console.log(content);
+        const helper = document.createElement('div');
+        helper.id = 'helper';
+        helper.innerHTML = content;
+        reScript(helper);
+        currentScript.after(...helper.childNodes);
+        currentScript.remove();
+    }})();
+}})();
+</script>"#,
+    nonce_attr, fetch_js);
+
+    if method_upper == "GET" {
+        if let Some(alt) = alt {
+            return js_code + &format!(r#"<noscript><a href="{}">{}</a></noscript>"#, escape_html(url), escape_html(alt));
+        }
+    }
+
+    js_code
+}
+
+/// Escapes a string for safe interpolation into inline `<script>` source,
+/// matching the approach used elsewhere for inlining data into SSR scripts:
+/// replacing `<` with its `\u003c` escape so a `</script>` sequence in the
+/// value can never terminate the surrounding block early.
+fn escape_for_script(s: &str) -> String {
+    s.replace('<', "\\u003c")
+}
+
+/// Escapes a string for safe interpolation into HTML markup, so a `"`, `<`,
+/// `>`, or `&` in the value can't break out of an attribute or tag.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 /// Sends an HTTP request to the provided URL using either the `GET` or `POST` method and returns the response as a String.
 /// This function handles asynchronous requests but executes them in a synchronous context using Tokio function `block_in_place`.
 /// Note: This function is slow. For better performance, consider using the fetch_shortcode_js function instead.
@@ -286,37 +555,565 @@ pub fn fetch_shortcode(
     let method = method.unwrap_or("GET");
     let json_body = json_body.unwrap_or("{}");
 
-    let data_to_route = async {
-        let response = match method.to_lowercase().as_str() {
-            "get" => CLIENT.get(url)
-                .send()
-                .await,
-            "post" => CLIENT.post(url)
-                .header("Content-Type", "application/json")
-                .body(json_body.to_owned())
-                .send()
-                .await,
-            _ => return format!("Invalid method: {}", method),
-        };
-
-        match response {
-            Ok(res) => {
-                if res.status().is_success() {
-                    res.text().await.unwrap_or_else(|_| "Failed to read response body".into())
-                } else {
-                    format!("Request failed with status: {}", res.status())
-                }
-            }
-            Err(e) => format!("Request error: {}", e),
-        }
-    };
+    match prefetch::intercept(url, method, json_body) {
+        Intercepted::NotIntercepted => {}
+        Intercepted::Collecting => return String::new(),
+        Intercepted::Resolved(Ok(body)) => return body,
+        Intercepted::Resolved(Err(err)) => return err,
+    }
 
     // Use `block_in_place` to run the async function
     // within the blocking context
-    tokio::task::block_in_place(||
+    let result = tokio::task::block_in_place(||
         // We need to access the current runtime to
         // run the async function
         tokio::runtime::Handle::current()
-            .block_on(data_to_route)
-    )
+            .block_on(do_fetch(&CLIENT, url, method, json_body))
+    );
+
+    match result {
+        Ok(body) => body,
+        Err(err) => err,
+    }
+}
+
+/// The method, body, headers, and optional bearer token for
+/// [`fetch_shortcode_with`] and [`fetch_shortcode_js_with`], which extend
+/// [`fetch_shortcode`] and [`fetch_shortcode_js`] beyond plain `GET`/`POST`
+/// for calling authenticated or RESTful backends.
+///
+/// # Example
+///
+/// ```rust
+/// use tera_shortcodes::FetchOptions;
+///
+/// let options = FetchOptions::new()
+///     .method("PUT")
+///     .json_body(r#"{"key": "value"}"#)
+///     .header("X-Request-Id", "abc123")
+///     .bearer_token("my-api-token");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    pub method: Option<String>,
+    pub json_body: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub bearer_token: Option<String>,
+}
+
+impl FetchOptions {
+
+    /// Creates an empty `FetchOptions`, defaulting to a headerless,
+    /// unauthenticated `GET` request with an empty JSON body.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the HTTP method: one of `GET`, `POST`, `PUT`, `PATCH`, or `DELETE`.
+    pub fn method(mut self, method: &str) -> Self {
+        self.method = Some(method.to_owned());
+        self
+    }
+
+    /// Sets the JSON request body, sent with every method except `GET`.
+    pub fn json_body(mut self, json_body: &str) -> Self {
+        self.json_body = Some(json_body.to_owned());
+        self
+    }
+
+    /// Adds a custom header, e.g. `header("X-Api-Key", "...")`.
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Sets the bearer token sent as `Authorization: Bearer <token>`.
+    pub fn bearer_token(mut self, token: &str) -> Self {
+        self.bearer_token = Some(token.to_owned());
+        self
+    }
+
+}
+
+/// Sends an HTTP request like [`fetch_shortcode`], but takes a
+/// [`FetchOptions`] so the request can carry custom headers and bearer
+/// auth, and use any of `GET`, `POST`, `PUT`, `PATCH`, or `DELETE` instead
+/// of only `GET`/`POST`.
+///
+/// # Parameters
+///
+/// - `url`: A string slice that holds the URL to which the HTTP request will be sent.
+/// - `options`: The method, body, headers, and optional bearer token to send. See [`FetchOptions`].
+///
+/// # Returns
+///
+/// A `String` containing either the response body from the server or an error message, exactly
+/// as described for [`fetch_shortcode`].
+///
+/// # Example
+///
+/// ```rust
+/// use tera_shortcodes::{fetch_shortcode_with, FetchOptions};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let response = fetch_shortcode_with(
+///         "https://example.com/api/data",
+///         &FetchOptions::new()
+///             .method("DELETE")
+///             .bearer_token("my-api-token"),
+///     );
+///
+///     println!("Response: {}", response);
+/// }
+/// ```
+pub fn fetch_shortcode_with(
+    url: &str,
+    options: &FetchOptions,
+) -> String {
+
+    if let Some(outcome) = prefetch::intercept_with(url, options) {
+        return outcome;
+    }
+
+    let result = tokio::task::block_in_place(||
+        tokio::runtime::Handle::current()
+            .block_on(do_fetch_with(&CLIENT, url, options))
+    );
+
+    match result {
+        Ok(body) => body,
+        Err(err) => err,
+    }
+}
+
+/// Checks (case-insensitively, as header names are) whether `headers`
+/// already sets `Content-Type`, so callers only inject the default
+/// `application/json` one when the caller hasn't supplied their own.
+fn has_content_type(headers: &HashMap<String, String>) -> bool {
+    headers.keys().any(|key| key.eq_ignore_ascii_case("content-type"))
+}
+
+/// Sends the actual HTTP request for a [`fetch_shortcode_with`] call,
+/// supporting `GET`, `POST`, `PUT`, `PATCH`, and `DELETE`, custom headers,
+/// and bearer auth. Returns `Ok` with the response body on success or `Err`
+/// with a descriptive message on failure, mirroring [`do_fetch`].
+async fn do_fetch_with(
+    client: &reqwest::Client,
+    url: &str,
+    options: &FetchOptions,
+) -> std::result::Result<String, String> {
+    let method = options.method.as_deref().unwrap_or("GET");
+    let json_body = options.json_body.as_deref().unwrap_or("{}");
+
+    let reqwest_method = match method.to_uppercase().as_str() {
+        "GET" => reqwest::Method::GET,
+        "POST" => reqwest::Method::POST,
+        "PUT" => reqwest::Method::PUT,
+        "PATCH" => reqwest::Method::PATCH,
+        "DELETE" => reqwest::Method::DELETE,
+        _ => return Err(format!("Invalid method: {}", method)),
+    };
+
+    let mut request = client.request(reqwest_method.clone(), url);
+
+    if reqwest_method != reqwest::Method::GET {
+        if !has_content_type(&options.headers) {
+            request = request.header("Content-Type", "application/json");
+        }
+        request = request.body(json_body.to_owned());
+    }
+
+    for (key, value) in &options.headers {
+        request = request.header(key, value);
+    }
+
+    if let Some(token) = &options.bearer_token {
+        request = request.bearer_auth(token);
+    }
+
+    match request.send().await {
+        Ok(res) => {
+            if res.status().is_success() {
+                Ok(res.text().await.unwrap_or_else(|_| "Failed to read response body".into()))
+            } else {
+                Err(format!("Request failed with status: {}", res.status()))
+            }
+        }
+        Err(e) => Err(format!("Request error: {}", e)),
+    }
+}
+
+/// Sends the actual HTTP request for a shortcode fetch, returning `Ok` with
+/// the response body on success or `Err` with a descriptive message on
+/// failure. Shared by [`fetch_shortcode`], [`fetch_shortcode_cached`], and
+/// the concurrent prefetch pass in [`render_with_shortcodes`], each of
+/// which decides for itself how to present or cache the two outcomes.
+async fn do_fetch(
+    client: &reqwest::Client,
+    url: &str,
+    method: &str,
+    json_body: &str,
+) -> std::result::Result<String, String> {
+    let response = match method.to_lowercase().as_str() {
+        "get" => client.get(url)
+            .send()
+            .await,
+        "post" => client.post(url)
+            .header("Content-Type", "application/json")
+            .body(json_body.to_owned())
+            .send()
+            .await,
+        _ => return Err(format!("Invalid method: {}", method)),
+    };
+
+    match response {
+        Ok(res) => {
+            if res.status().is_success() {
+                Ok(res.text().await.unwrap_or_else(|_| "Failed to read response body".into()))
+            } else {
+                Err(format!("Request failed with status: {}", res.status()))
+            }
+        }
+        Err(e) => Err(format!("Request error: {}", e)),
+    }
+}
+
+/// Sends an HTTP request like [`fetch_shortcode`], but first checks (and,
+/// on success, populates) a shared in-memory cache keyed by
+/// `(url, method, json_body)`, so a shortcode fetched repeatedly within
+/// `ttl` is served from memory instead of hitting the backend again.
+///
+/// Only successful responses are cached; error bodies are always fetched
+/// fresh. The cache holds at most 256 entries by default; change that cap
+/// for the process with [`Shortcodes::with_cache`]. Once the cap is reached,
+/// the oldest entry is evicted to make room.
+///
+/// # Parameters
+///
+/// - `url`: A string slice that holds the URL to which the HTTP request will be sent.
+/// - `method`: An optional HTTP method, either `GET` or `POST`. Defaults to `GET` if `None` is provided.
+/// - `json_body`: An optional JSON string to be used as the request body for `POST` requests.
+///   Defaults to an empty JSON object (`{}`) if `None` is provided. This parameter is ignored for `GET` requests.
+/// - `ttl`: How long a cached response stays fresh before it's fetched again.
+///
+/// # Returns
+///
+/// A `String` containing either the (possibly cached) response body, or an error message, exactly
+/// as described for [`fetch_shortcode`].
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tera_shortcodes::fetch_shortcode_cached;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let response = fetch_shortcode_cached(
+///         "https://example.com/products",
+///         Some("GET"),
+///         None,
+///         Duration::from_secs(60),
+///     );
+///
+///     println!("Response: {}", response);
+/// }
+/// ```
+pub fn fetch_shortcode_cached(
+    url: &str,
+    method: Option<&str>,
+    json_body: Option<&str>,
+    ttl: Duration,
+) -> String {
+
+    let method = method.unwrap_or("GET");
+    let json_body = json_body.unwrap_or("{}");
+    let key: FetchKey = (url.to_owned(), method.to_owned(), json_body.to_owned());
+
+    if let Some(body) = CACHE.lock().unwrap().get(&key, ttl) {
+        return body;
+    }
+
+    match prefetch::intercept(url, method, json_body) {
+        Intercepted::NotIntercepted => {}
+        Intercepted::Collecting => return String::new(),
+        Intercepted::Resolved(Ok(body)) => {
+            CACHE.lock().unwrap().insert(key, body.clone(), ttl);
+            return body;
+        }
+        Intercepted::Resolved(Err(err)) => return err,
+    }
+
+    let result = tokio::task::block_in_place(||
+        tokio::runtime::Handle::current()
+            .block_on(do_fetch(&CLIENT, url, method, json_body))
+    );
+
+    match result {
+        Ok(body) => {
+            CACHE.lock().unwrap().insert(key, body.clone(), ttl);
+            body
+        }
+        Err(err) => err,
+    }
+}
+
+/// A cached response body together with the `Instant` it was inserted at,
+/// used to determine whether it's still within a lookup's TTL.
+struct CacheEntry {
+    body: String,
+    inserted_at: std::time::Instant,
+}
+
+/// The in-memory store backing [`fetch_shortcode_cached`]: a bounded map of
+/// `FetchKey` to [`CacheEntry`], with the oldest entry evicted once the
+/// configured maximum is reached.
+struct ResponseCache {
+    entries: HashMap<FetchKey, CacheEntry>,
+    max_entries: usize,
+}
+
+impl ResponseCache {
+    fn new(max_entries: usize) -> Self {
+        ResponseCache { entries: HashMap::new(), max_entries }
+    }
+
+    fn get(&mut self, key: &FetchKey, ttl: Duration) -> Option<String> {
+        match self.entries.get(key) {
+            Some(entry) if entry.inserted_at.elapsed() < ttl => Some(entry.body.clone()),
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: FetchKey, body: String, ttl: Duration) {
+        self.entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+
+        while self.entries.len() >= self.max_entries {
+            match self.entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted_at)
+                .map(|(key, _)| key.clone())
+            {
+                Some(oldest) => { self.entries.remove(&oldest); }
+                None => break,
+            }
+        }
+
+        self.entries.insert(key, CacheEntry { body, inserted_at: std::time::Instant::now() });
+    }
+}
+
+/// Default cap on how many distinct responses [`fetch_shortcode_cached`]
+/// keeps in memory before evicting the oldest one; overridden by
+/// [`Shortcodes::with_cache`].
+const DEFAULT_CACHE_MAX_ENTRIES: usize = 256;
+
+static CACHE: Lazy<std::sync::Mutex<ResponseCache>> =
+    Lazy::new(|| std::sync::Mutex::new(ResponseCache::new(DEFAULT_CACHE_MAX_ENTRIES)));
+
+/// A server-side fetch a shortcode function intends to make, recorded
+/// during the collection pass of [`render_with_shortcodes`] instead of
+/// being executed immediately.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PendingFetch {
+    pub url: String,
+    pub method: String,
+    pub body: String,
+}
+
+/// The `(url, method, body)` triple a [`PendingFetch`] resolves to, used to
+/// de-duplicate identical requests and to look up their resolved bodies.
+pub type FetchKey = (String, String, String);
+
+/// The fully-qualified key identifying a [`fetch_shortcode_with`] request.
+/// Unlike [`FetchKey`], this also folds in the sorted custom headers and
+/// bearer token from the originating [`FetchOptions`], so two requests to
+/// the same URL with different auth/headers are never conflated during
+/// prefetch collection or resolution.
+type FetchWithKey = (String, String, String, Vec<(String, String)>, Option<String>);
+
+fn fetch_with_key(url: &str, options: &FetchOptions) -> FetchWithKey {
+    let method = options.method.as_deref().unwrap_or("GET").to_owned();
+    let body = options.json_body.as_deref().unwrap_or("{}").to_owned();
+    let mut headers: Vec<(String, String)> = options.headers.iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    headers.sort();
+    (url.to_owned(), method, body, headers, options.bearer_token.clone())
+}
+
+/// What [`prefetch::intercept`] found a call to [`fetch_shortcode`] or
+/// [`fetch_shortcode_cached`] should do instead of fetching directly.
+pub(crate) enum Intercepted {
+    /// No prefetch pass is active; the caller should fetch as normal.
+    NotIntercepted,
+    /// A collection pass is active and this fetch has been recorded as
+    /// pending; the caller should return a throwaway placeholder, since
+    /// this render is discarded.
+    Collecting,
+    /// A resolved pass is active and this fetch already has a result,
+    /// exactly as [`do_fetch`] would have returned it directly, including
+    /// whether it succeeded or failed.
+    Resolved(std::result::Result<String, String>),
+}
+
+mod prefetch {
+    use super::{do_fetch, do_fetch_with, fetch_with_key, FetchKey, FetchOptions, FetchWithKey, Intercepted, PendingFetch, CLIENT};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    enum Mode {
+        Direct,
+        Collecting(Vec<PendingFetch>, Vec<(String, FetchOptions)>),
+        Resolved(HashMap<FetchKey, std::result::Result<String, String>>, HashMap<FetchWithKey, String>),
+    }
+
+    thread_local! {
+        static MODE: RefCell<Mode> = const { RefCell::new(Mode::Direct) };
+    }
+
+    /// Called from [`super::fetch_shortcode`] and [`super::fetch_shortcode_cached`]
+    /// before either would otherwise block on the network. See
+    /// [`Intercepted`] for what the caller should do with the result; the
+    /// `Ok`/`Err` distinction from the original fetch is preserved so
+    /// [`super::fetch_shortcode_cached`] never caches an error string.
+    pub(super) fn intercept(url: &str, method: &str, json_body: &str) -> Intercepted {
+        MODE.with(|mode| match &mut *mode.borrow_mut() {
+            Mode::Direct => Intercepted::NotIntercepted,
+            Mode::Collecting(pending, _) => {
+                pending.push(PendingFetch {
+                    url: url.to_owned(),
+                    method: method.to_owned(),
+                    body: json_body.to_owned(),
+                });
+                Intercepted::Collecting
+            }
+            Mode::Resolved(responses, _) => {
+                let key = (url.to_owned(), method.to_owned(), json_body.to_owned());
+                Intercepted::Resolved(responses.get(&key).cloned().unwrap_or_else(|| Ok(String::new())))
+            }
+        })
+    }
+
+    /// Like [`intercept`], but for [`super::fetch_shortcode_with`], which
+    /// carries a [`FetchOptions`] (custom headers, bearer token) instead of
+    /// a bare `(method, body)` pair. `fetch_shortcode_with` has no cache to
+    /// protect from error strings, so this keeps flattening `Ok`/`Err` into
+    /// a plain body the way it already does outside of a prefetch pass.
+    pub(super) fn intercept_with(url: &str, options: &FetchOptions) -> Option<String> {
+        MODE.with(|mode| match &mut *mode.borrow_mut() {
+            Mode::Direct => None,
+            Mode::Collecting(_, pending) => {
+                pending.push((url.to_owned(), options.clone()));
+                Some(String::new())
+            }
+            Mode::Resolved(_, responses) => {
+                let key = fetch_with_key(url, options);
+                Some(responses.get(&key).cloned().unwrap_or_default())
+            }
+        })
+    }
+
+    /// Runs `render` once in collection mode, gathering every shortcode
+    /// fetch it would have made without performing any of them.
+    pub(super) fn collect(render: impl FnOnce()) -> (Vec<PendingFetch>, Vec<(String, FetchOptions)>) {
+        MODE.with(|mode| *mode.borrow_mut() = Mode::Collecting(Vec::new(), Vec::new()));
+        render();
+        MODE.with(|mode| match mode.replace(Mode::Direct) {
+            Mode::Collecting(pending, pending_with) => (pending, pending_with),
+            _ => (Vec::new(), Vec::new()),
+        })
+    }
+
+    /// Resolves every distinct pending fetch concurrently against the
+    /// shared client, then runs `render` again with shortcode fetches
+    /// reading their bodies out of the resolved maps instead of blocking.
+    pub(super) async fn resolve_and_render(
+        pending: Vec<PendingFetch>,
+        pending_with: Vec<(String, FetchOptions)>,
+        render: impl FnOnce() -> tera::Result<String>,
+    ) -> tera::Result<String> {
+        let mut keys: Vec<FetchKey> = Vec::new();
+        for fetch in pending {
+            let key = (fetch.url, fetch.method, fetch.body);
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        let bodies = futures::future::join_all(
+            keys.iter().map(|(url, method, body)| do_fetch(&CLIENT, url, method, body))
+        ).await;
+        let resolved: HashMap<FetchKey, std::result::Result<String, String>> = keys.into_iter().zip(bodies).collect();
+
+        let mut with_keys: Vec<FetchWithKey> = Vec::new();
+        let mut with_options: Vec<(String, FetchOptions)> = Vec::new();
+        for (url, options) in pending_with {
+            let key = fetch_with_key(&url, &options);
+            if !with_keys.contains(&key) {
+                with_keys.push(key);
+                with_options.push((url, options));
+            }
+        }
+
+        let with_bodies = futures::future::join_all(
+            with_options.iter().map(|(url, options)| do_fetch_with(&CLIENT, url, options))
+        ).await;
+        let with_bodies = with_bodies.into_iter().map(|result| result.unwrap_or_else(|err| err));
+        let resolved_with: HashMap<FetchWithKey, String> = with_keys.into_iter().zip(with_bodies).collect();
+
+        MODE.with(|mode| *mode.borrow_mut() = Mode::Resolved(resolved, resolved_with));
+        let rendered = render();
+        MODE.with(|mode| *mode.borrow_mut() = Mode::Direct);
+
+        rendered
+    }
+}
+
+/// Renders a Tera template whose shortcodes fetch server-side data,
+/// resolving all of those fetches concurrently instead of blocking the
+/// render on each one in turn.
+///
+/// This runs the template twice: a first, throwaway pass that collects
+/// every fetch a shortcode function would have made (via
+/// [`fetch_shortcode`], [`fetch_shortcode_cached`], or
+/// [`fetch_shortcode_with`]) without performing any of them, then awaits
+/// them all together with `futures::future::join_all`, de-duplicating
+/// identical requests; a second pass renders the real output with those
+/// fetches already resolved, so no shortcode blocks on the network during
+/// it.
+///
+/// Only the three `fetch_shortcode*` helpers are intercepted during the
+/// throwaway pass, so a shortcode function with a side effect beyond one of
+/// those fetches (a counter, a log line, a direct database write) still
+/// runs twice per render; keep such side effects out of shortcode handlers
+/// used with this function, or perform them from the resolved body instead
+/// of from the handler itself.
+///
+/// # Parameters
+///
+/// - `tera`: The `Tera` instance to render with, with a [`Shortcodes`]
+///   instance already registered as its `"shortcode"` function.
+/// - `template`: The name of the template to render.
+/// - `ctx`: The context to render it with.
+///
+/// # Returns
+///
+/// The rendered output, or a `tera::Error` if the second pass fails to
+/// render. An error from the throwaway first pass is ignored.
+pub async fn render_with_shortcodes(
+    tera: &tera::Tera,
+    template: &str,
+    ctx: &tera::Context,
+) -> Result<String> {
+    let (pending, pending_with) = prefetch::collect(|| {
+        let _ = tera.render(template, ctx);
+    });
+
+    prefetch::resolve_and_render(pending, pending_with, || tera.render(template, ctx)).await
 }
\ No newline at end of file
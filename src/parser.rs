@@ -0,0 +1,344 @@
+//
+// bracket shortcode parser
+//
+
+use std::collections::HashMap;
+use crate::ShortcodeFn;
+
+/// A single lexical piece of a raw content string: either literal text or one
+/// half of a `[name attr="v"]...[/name]` pair.
+///
+/// The `raw` fields keep the original, unparsed bracket contents so an
+/// unregistered shortcode name can be written back out byte-for-byte instead
+/// of being re-serialized from its parsed attributes.
+enum Token<'a> {
+    Text(&'a str),
+    Open { name: String, attrs: HashMap<String, tera::Value>, raw: &'a str },
+    Close { name: String, raw: &'a str },
+}
+
+/// Expands WordPress-style bracket shortcodes found in `raw` using the
+/// registered `functions`, recursively expanding any shortcodes nested in
+/// the enclosed content.
+pub(crate) fn expand(functions: &HashMap<String, ShortcodeFn>, raw: &str) -> String {
+    let tokens = tokenize(raw);
+    let mut pos = 0;
+    render(&tokens, &mut pos, None, functions).0
+}
+
+/// Renders tokens starting at `*pos` until either the input is exhausted or,
+/// when `until` is `Some(name)`, a matching `[/name]` closing tag is found
+/// (which is consumed). Returns the rendered text plus the raw contents of
+/// the sought closing tag if it was actually found, so callers can tell an
+/// enclosing shortcode apart from a self-closing one.
+///
+/// Closing tags are matched by nearest-first, not by name-aware nesting: an
+/// unclosed `[bold]` immediately followed by a fully-matched, same-named
+/// `[bold]...[/bold]` pair has its own close "stolen" by the inner pair, so
+/// the outer tag is treated as self-closing and its would-be content is left
+/// as plain trailing text instead of being reported as malformed input. Give
+/// same-named enclosing shortcodes their own `[/name]` rather than relying
+/// on balanced nesting if this matters to you.
+fn render<'a>(
+    tokens: &[Token<'a>],
+    pos: &mut usize,
+    until: Option<&str>,
+    functions: &HashMap<String, ShortcodeFn>,
+) -> (String, Option<&'a str>) {
+    let mut out = String::new();
+
+    while *pos < tokens.len() {
+        match &tokens[*pos] {
+            Token::Text(text) => {
+                out.push_str(text);
+                *pos += 1;
+            }
+            Token::Close { name, raw } => {
+                match until {
+                    Some(expected) if expected == name => {
+                        *pos += 1;
+                        return (out, Some(*raw));
+                    }
+                    Some(_) => {
+                        // Belongs to an ancestor; leave it for them to consume.
+                        return (out, None);
+                    }
+                    None => {
+                        // No enclosing shortcode is waiting for this close, so
+                        // the brackets are just literal, unmatched text.
+                        out.push('[');
+                        out.push_str(raw);
+                        out.push(']');
+                        *pos += 1;
+                    }
+                }
+            }
+            Token::Open { name, attrs, raw } => {
+                *pos += 1;
+                let (inner, close_raw) = render(tokens, pos, Some(name), functions);
+                let found = close_raw.is_some();
+
+                let rendered = match functions.get(name.as_str()) {
+                    Some(shortcode_fn) => {
+                        let content = if found { Some(inner.as_str()) } else { None };
+                        match shortcode_fn(attrs, content) {
+                            Ok(fragment) => fragment,
+                            Err(err) => format!("[shortcode error in \"{}\": {}]", name, err),
+                        }
+                    }
+                    None if found => format!("[{}]{}[{}]", raw, inner, close_raw.unwrap_or_default()),
+                    None => format!("[{}]", raw),
+                };
+
+                out.push_str(&rendered);
+                if !found {
+                    // `name` was self-closing: `inner` is just the ordinary
+                    // content that followed it, not anything it encloses.
+                    // Keep looping instead of returning, since the token
+                    // that stopped the search above may be our own `until`.
+                    out.push_str(&inner);
+                }
+            }
+        }
+    }
+
+    (out, None)
+}
+
+/// Splits `raw` into a flat sequence of text and tag tokens, handling the
+/// `[[name]]` escape (a literal `[name]` that is never treated as a tag).
+fn tokenize(raw: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let bytes = raw.as_bytes();
+    let len = bytes.len();
+    let mut text_start = 0usize;
+    let mut i = 0usize;
+
+    while i < len {
+        if bytes[i] != b'[' {
+            i += 1;
+            continue;
+        }
+
+        if bytes.get(i + 1) == Some(&b'[') {
+            if let Some(rel_end) = raw[i + 2..].find("]]") {
+                let end = i + 2 + rel_end;
+                if text_start < i {
+                    tokens.push(Token::Text(&raw[text_start..i]));
+                }
+                tokens.push(Token::Text(&raw[i + 1..end + 1]));
+                i = end + 2;
+                text_start = i;
+                continue;
+            }
+        }
+
+        if let Some((tag_end, inner)) = scan_tag(raw, i) {
+            if text_start < i {
+                tokens.push(Token::Text(&raw[text_start..i]));
+            }
+            if let Some(name) = inner.strip_prefix('/') {
+                tokens.push(Token::Close { name: name.trim().to_string(), raw: inner });
+            } else {
+                let (name, attrs) = parse_name_and_attrs(inner);
+                tokens.push(Token::Open { name, attrs, raw: inner });
+            }
+            i = tag_end;
+            text_start = i;
+            continue;
+        }
+
+        i += 1;
+    }
+
+    if text_start < len {
+        tokens.push(Token::Text(&raw[text_start..]));
+    }
+
+    tokens
+}
+
+/// Looks for the unquoted `]` that closes the tag opened at `raw[open] == '['`.
+/// Returns the index just past that `]` plus the bracket contents, or `None`
+/// if `[` doesn't start something that looks like a shortcode tag.
+fn scan_tag(raw: &str, open: usize) -> Option<(usize, &str)> {
+    let bytes = raw.as_bytes();
+    let len = bytes.len();
+    let content_start = open + 1;
+    let mut quote: Option<u8> = None;
+    let mut i = content_start;
+
+    while i < len {
+        let b = bytes[i];
+        match quote {
+            Some(q) => {
+                if b == q {
+                    quote = None;
+                }
+            }
+            None => match b {
+                b'"' | b'\'' => quote = Some(b),
+                b'[' => return None,
+                b']' => {
+                    let inner = &raw[content_start..i];
+                    return is_tag_name(inner).then_some((i + 1, inner));
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+
+    None
+}
+
+/// A shortcode tag's bracket contents must start with an ASCII letter, digit
+/// or `_` (optionally preceded by `/` for a closing tag), so bracketed text
+/// such as `[ a note ]` or `[...]` isn't mistaken for a shortcode.
+fn is_tag_name(inner: &str) -> bool {
+    let inner = inner.strip_prefix('/').unwrap_or(inner);
+    match inner.chars().next() {
+        Some(c) => c.is_ascii_alphanumeric() || c == '_',
+        None => false,
+    }
+}
+
+/// Parses `name attr="v" other='w'` into the shortcode name and an attribute
+/// map, stripping the surrounding quotes the same way registered functions
+/// already do for their arguments.
+fn parse_name_and_attrs(inner: &str) -> (String, HashMap<String, tera::Value>) {
+    let tokens = split_attr_tokens(inner);
+    let mut tokens = tokens.into_iter();
+
+    let name = tokens.next().unwrap_or_default().trim_end_matches('/').to_string();
+
+    let mut attrs = HashMap::new();
+    for token in tokens {
+        let token = token.trim_end_matches('/');
+        if let Some(eq) = token.find('=') {
+            let key = token[..eq].to_string();
+            let value = token[eq + 1..].trim_matches(|c| c == '"' || c == '\'');
+            attrs.insert(key, tera::Value::String(value.to_string()));
+        }
+    }
+
+    (name, attrs)
+}
+
+/// Splits a tag's inner contents on whitespace, keeping `key="a value"`
+/// together even when the quoted value itself contains spaces.
+fn split_attr_tokens(inner: &str) -> Vec<&str> {
+    let bytes = inner.as_bytes();
+    let len = bytes.len();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < len {
+        while i < len && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= len {
+            break;
+        }
+        let start = i;
+        while i < len && !bytes[i].is_ascii_whitespace() {
+            if bytes[i] == b'=' {
+                i += 1;
+                if i < len && (bytes[i] == b'"' || bytes[i] == b'\'') {
+                    let quote = bytes[i];
+                    i += 1;
+                    while i < len && bytes[i] != quote {
+                        i += 1;
+                    }
+                    if i < len {
+                        i += 1;
+                    }
+                    break;
+                }
+                // Unquoted value: keep scanning to the next whitespace
+                // instead of stopping right after the `=`.
+                continue;
+            }
+            i += 1;
+        }
+        tokens.push(&inner[start..i]);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap_fn(tag: &'static str) -> (String, ShortcodeFn) {
+        (tag.to_owned(), Box::new(move |_attrs, content| {
+            Ok(format!("<{}>{}</{}>", tag, content.unwrap_or(""), tag))
+        }))
+    }
+
+    #[test]
+    fn expands_shortcodes_nested_inside_a_different_name() {
+        let functions: HashMap<String, ShortcodeFn> =
+            HashMap::from([wrap_fn("outer"), wrap_fn("inner")]);
+        let html = expand(&functions, "[outer]a [inner]b[/inner] c[/outer]");
+        assert_eq!(html, "<outer>a <inner>b</inner> c</outer>");
+    }
+
+    #[test]
+    fn properly_nested_same_name_shortcodes_close_correctly() {
+        let functions: HashMap<String, ShortcodeFn> = HashMap::from([wrap_fn("bold")]);
+        let html = expand(&functions, "[bold]a [bold]b[/bold] c[/bold]");
+        assert_eq!(html, "<bold>a <bold>b</bold> c</bold>");
+    }
+
+    #[test]
+    fn parses_an_unquoted_attribute_value() {
+        let functions: HashMap<String, ShortcodeFn> = HashMap::from([(
+            "shortcode".to_owned(),
+            Box::new(|attrs: &HashMap<String, tera::Value>, _content: Option<&str>| {
+                Ok(attrs.get("attr").and_then(|v| v.as_str()).unwrap_or("").to_owned())
+            }) as ShortcodeFn,
+        )]);
+        let html = expand(&functions, "[shortcode attr=value]");
+        assert_eq!(html, "value");
+    }
+
+    #[test]
+    fn self_closing_shortcode_has_no_content() {
+        let functions: HashMap<String, ShortcodeFn> = HashMap::from([wrap_fn("hr")]);
+        let html = expand(&functions, "before [hr] after");
+        assert_eq!(html, "before <hr></hr> after");
+    }
+
+    #[test]
+    fn unknown_shortcode_name_passed_through_untouched() {
+        let functions: HashMap<String, ShortcodeFn> = HashMap::new();
+        let html = expand(&functions, "[gallery]pics[/gallery]");
+        assert_eq!(html, "[gallery]pics[/gallery]");
+    }
+
+    #[test]
+    fn escaped_double_brackets_become_a_literal_tag() {
+        let functions: HashMap<String, ShortcodeFn> = HashMap::from([wrap_fn("gallery")]);
+        let html = expand(&functions, "[[gallery]]");
+        assert_eq!(html, "[gallery]");
+    }
+
+    #[test]
+    fn unmatched_closing_tag_is_left_as_literal_text() {
+        let functions: HashMap<String, ShortcodeFn> = HashMap::new();
+        let html = expand(&functions, "text [/gallery] more");
+        assert_eq!(html, "text [/gallery] more");
+    }
+
+    #[test]
+    fn unclosed_shortcode_followed_by_same_name_pair_steals_the_close() {
+        // Documents the quirk described on `render`: a single later
+        // "[/bold]" is claimed by the nested same-named pair, not the
+        // outer unclosed tag, which is treated as self-closing instead.
+        let functions: HashMap<String, ShortcodeFn> = HashMap::from([wrap_fn("bold")]);
+        let html = expand(&functions, "[bold]outer text [bold]inner[/bold] tail");
+        assert_eq!(html, "<bold></bold>outer text <bold>inner</bold> tail");
+    }
+}